@@ -1,28 +1,35 @@
 use std::{
     env,
     fs::{self, File},
-    io::{Read, Write},
-    path::PathBuf,
-    process::{exit, Command},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{exit, Command, Stdio},
+    thread,
 };
 
 use anyhow::{bail, Context, Result};
 use bzip2::read::MultiBzDecoder;
-use clap::Parser;
-use flate2::read::GzDecoder;
+use clap::{Parser, ValueEnum};
+use flate2::read::MultiGzDecoder;
 use lzma_rust2::{LzmaReader, XzReader};
 use once_cell::sync::Lazy;
+use tar::Archive;
 use zip::ZipArchive;
 
+/// Double extensions that need to be recognized as a whole, since
+/// `Path::extension` only ever returns the last component (`"gz"` for
+/// `foo.tar.gz`, losing the `tar` layer).
+const COMPOUND_EXTENSIONS: [&str; 3] = ["tar.gz", "tar.xz", "tar.bz2"];
+
 #[derive(Debug, Parser)]
 #[command(name = "img2kvm", about = "A utility that convert disk image in Proxmox VE.")]
 struct Parameter {
     /// The name of image file, e.g. openwrt-24.10.2-x86-64-generic-squashfs-combined-efi.img.
-    /// Supported ending with 7z, bz2, bzip2, gz, lzma, xz, and zip extensions file.
+    /// Supported ending with bz2, bzip2, gz, lzma, tar, tar.gz, tar.xz, tar.bz2, xz, and zip extensions file.
     #[arg(
         short = 'n',
         long = "image-name",
-        help = "the name of image file, e.g. openwrt-24.10.2-x86-64-generic-squashfs-combined-efi.img.\nSupported ending with 7z, bz2, bzip2, gz, lzma, xz, and zip extensions file."
+        help = "the name of image file, e.g. openwrt-24.10.2-x86-64-generic-squashfs-combined-efi.img.\nSupported ending with bz2, bzip2, gz, lzma, tar, tar.gz, tar.xz, tar.bz2, xz, and zip extensions file."
     )]
     image_name: PathBuf,
 
@@ -33,6 +40,56 @@ struct Parameter {
     /// Storage pool of Proxmox VE.
     #[arg(short = 's', long, default_value = "local-lvm", help = "Storage pool of Proxmox VE.")]
     storage: String,
+
+    /// List the entries inside a multi-file archive instead of converting.
+    #[arg(long = "list", help = "List the entries inside a multi-file archive (e.g. a ZIP) instead of converting.")]
+    list: bool,
+
+    /// Select a specific entry inside a multi-file archive by name or index.
+    #[arg(
+        long = "entry",
+        help = "Select a specific entry inside a multi-file archive by name or index, e.g. 'disk.img' or '2'."
+    )]
+    entry: Option<String>,
+
+    /// Bus to attach the imported disk as once importdisk succeeds, e.g. 'scsi'.
+    #[arg(
+        long = "bus",
+        value_enum,
+        help = "Attach the imported disk to the VM on this bus (scsi, virtio, or sata) once importdisk succeeds."
+    )]
+    bus: Option<Bus>,
+
+    /// Disk slot to attach the imported disk to when --bus is set, e.g. '0'.
+    #[arg(
+        long = "disk-slot",
+        requires = "bus",
+        default_value_t = 0,
+        help = "Disk slot to attach the imported disk to when --bus is set, e.g. '0'."
+    )]
+    disk_slot: u32,
+
+    /// Set the imported disk as the first boot device. Requires --bus.
+    #[arg(long = "boot", requires = "bus", help = "Set the imported disk as the first boot device. Requires --bus.")]
+    boot: bool,
+}
+
+/// The Proxmox VE bus an imported disk can be attached to.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Bus {
+    Scsi,
+    Virtio,
+    Sata,
+}
+
+impl Bus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Bus::Scsi => "scsi",
+            Bus::Virtio => "virtio",
+            Bus::Sata => "sata",
+        }
+    }
 }
 
 static WORK_DIR: Lazy<PathBuf> = Lazy::new(|| env::current_dir().expect("Failed to get current directory"));
@@ -54,38 +111,148 @@ fn run(parameter: Parameter) -> Result<()> {
 
     let image_path = dunce::canonicalize(image_path).context("Failed to canonicalize with dunce")?;
 
-    let extension = image_path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .map(|s| s.to_lowercase())
-        .context("File has no valid extension")?;
-
-    let mut is_image_file = false;
-    let processed_image_path = match extension.as_str() {
-        "bz2" | "bzip2" => decompress_bz2_file(image_path)?,
-        "gz" => decompress_gz_file(image_path)?,
-        "lzma" => decompress_lzma_file(image_path)?,
-        "xz" => decompress_xz_file(image_path)?,
-        "zip" => decompress_zip_file(image_path)?,
-        "img" | "iso" => {
-            is_image_file = true;
-            image_path
-        }
-        _ => bail!("Unsupported file extension: {}", extension),
+    let extension = detect_extension_hint(&image_path);
+
+    // The extension is only a hint; the magic bytes are the source of truth
+    // whenever they unambiguously identify a known compression format. A tar
+    // layer is the exception: magic sniffing only sees the outer compressor,
+    // so a recognized `tar*` extension always wins over it.
+    let format = match &extension {
+        Some(hint) if hint.starts_with("tar") => hint.clone(),
+        _ => detect_format_by_magic(&image_path)?
+            .map(String::from)
+            .unwrap_or_else(|| extension.clone().unwrap_or_else(|| "img".to_string())),
     };
 
+    if parameter.list {
+        return match format.as_str() {
+            "zip" => list_zip_entries(&image_path),
+            "tar" | "tar.gz" | "tar.xz" | "tar.bz2" => list_tar_entries(&image_path, &format),
+            _ => bail!("--list is only supported for ZIP and tar archives"),
+        };
+    }
+
     let vmdisk_name = WORK_DIR.join("img2kvm_temp.qcow2");
 
-    // Convert image to qcow2 format
+    // Convert image to qcow2 format. Single-stream compressed formats (bz2,
+    // gz, lzma, xz) are streamed straight into qemu-img's stdin so a
+    // multi-gigabyte image is never buffered or written out in full; if the
+    // stream can't be consumed we fall back to decompressing to a temp file
+    // first. The chosen ZIP or tar entry is always extracted to a temp file.
     println!("--- convert img to qcow2...");
+    let temp_file_to_clean = match format.as_str() {
+        "bz2" | "bzip2" => {
+            if let Err(err) = convert_stream_to_qcow2(open_bz2_decoder(&image_path)?, &vmdisk_name) {
+                eprintln!("Streaming conversion failed ({}), falling back to temp file...", err);
+                let decompressed = decompress_bz2_file(&image_path)?;
+                convert_file_to_qcow2(&decompressed, &vmdisk_name)?;
+                Some(decompressed)
+            } else {
+                None
+            }
+        }
+        "gz" => {
+            if let Err(err) = convert_stream_to_qcow2(open_gz_decoder(&image_path)?, &vmdisk_name) {
+                eprintln!("Streaming conversion failed ({}), falling back to temp file...", err);
+                let decompressed = decompress_gz_file(&image_path)?;
+                convert_file_to_qcow2(&decompressed, &vmdisk_name)?;
+                Some(decompressed)
+            } else {
+                None
+            }
+        }
+        "lzma" => {
+            if let Err(err) = convert_stream_to_qcow2(open_lzma_decoder(&image_path)?, &vmdisk_name) {
+                eprintln!("Streaming conversion failed ({}), falling back to temp file...", err);
+                let decompressed = decompress_lzma_file(&image_path)?;
+                convert_file_to_qcow2(&decompressed, &vmdisk_name)?;
+                Some(decompressed)
+            } else {
+                None
+            }
+        }
+        "xz" => {
+            if let Err(err) = convert_stream_to_qcow2(open_xz_decoder(&image_path)?, &vmdisk_name) {
+                eprintln!("Streaming conversion failed ({}), falling back to temp file...", err);
+                let decompressed = decompress_xz_file(&image_path)?;
+                convert_file_to_qcow2(&decompressed, &vmdisk_name)?;
+                Some(decompressed)
+            } else {
+                None
+            }
+        }
+        "zip" => {
+            let decompressed = decompress_zip_file(&image_path, parameter.entry.as_deref())?;
+            convert_file_to_qcow2(&decompressed, &vmdisk_name)?;
+            Some(decompressed)
+        }
+        "tar" | "tar.gz" | "tar.xz" | "tar.bz2" => {
+            let decompressed = decompress_tar_file(&image_path, &format, parameter.entry.as_deref())?;
+            convert_file_to_qcow2(&decompressed, &vmdisk_name)?;
+            Some(decompressed)
+        }
+        "img" | "iso" => {
+            convert_file_to_qcow2(&image_path, &vmdisk_name)?;
+            None
+        }
+        _ => {
+            // Neither magic nor extension matched a known format; assume it's
+            // already a raw disk image.
+            convert_file_to_qcow2(&image_path, &vmdisk_name)?;
+            None
+        }
+    };
+
+    // Import disk to VM
+    println!("--- importdisk...");
+    let output = Command::new("qm")
+        .arg("importdisk")
+        .arg(parameter.vm_id.to_string())
+        .arg(&vmdisk_name)
+        .arg(parameter.storage)
+        .output()
+        .context("Failed to execute qm command")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("qm importdisk failed: {}", stderr);
+    }
+    println!("{}", String::from_utf8_lossy(&output.stdout));
+
+    // Attach the imported disk to the VM
+    if let Some(bus) = parameter.bus {
+        println!("--- attach imported disk...");
+        let volume_id = resolve_imported_volume_id(&String::from_utf8_lossy(&output.stdout), parameter.vm_id)?;
+        attach_disk(parameter.vm_id, bus, parameter.disk_slot, &volume_id)?;
+
+        if parameter.boot {
+            set_boot_disk(parameter.vm_id, bus, parameter.disk_slot)?;
+        }
+    }
+
+    // Clean up temporary files
+    println!("--- remove temp file...");
+    fs::remove_file(&vmdisk_name).context("Failed to remove temporary qcow2 file")?;
+
+    if let Some(temp_file) = temp_file_to_clean {
+        fs::remove_file(&temp_file).context("Failed to remove decompressed image file")?;
+    }
+
+    println!("--- success");
+    Ok(())
+}
+
+/// Converts an already-on-disk raw image to qcow2 by passing its path
+/// directly to `qemu-img convert`.
+fn convert_file_to_qcow2(image_path: &Path, vmdisk_name: &Path) -> Result<()> {
     let output = Command::new("qemu-img")
         .arg("convert")
         .arg("-f")
         .arg("raw")
         .arg("-O")
         .arg("qcow2")
-        .arg(&processed_image_path)
-        .arg(&vmdisk_name)
+        .arg(image_path)
+        .arg(vmdisk_name)
         .output()
         .context("Failed to execute qemu-img command")?;
 
@@ -95,113 +262,296 @@ fn run(parameter: Parameter) -> Result<()> {
     }
     println!("{}", String::from_utf8_lossy(&output.stdout));
 
-    // Import disk to VM
-    println!("--- importdisk...");
+    Ok(())
+}
+
+/// Converts a raw image to qcow2 by feeding `reader` directly into
+/// `qemu-img convert`'s stdin on a background thread, so the decompressed
+/// bytes never need to be fully buffered or written to an intermediate file.
+///
+/// `qemu-img` has no `-` convention for "read the source from stdin"; it
+/// treats `-` as a literal filename and fails. `/dev/stdin` is what actually
+/// resolves to the piped data, so that's what we pass as the source.
+fn convert_stream_to_qcow2(mut reader: Box<dyn Read + Send>, vmdisk_name: &Path) -> Result<()> {
+    let mut child = Command::new("qemu-img")
+        .arg("convert")
+        .arg("-f")
+        .arg("raw")
+        .arg("-O")
+        .arg("qcow2")
+        .arg("/dev/stdin")
+        .arg(vmdisk_name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to spawn qemu-img command")?;
+
+    let mut stdin = child.stdin.take().context("Failed to open qemu-img stdin")?;
+    let writer = thread::spawn(move || -> Result<()> {
+        io::copy(&mut reader, &mut stdin).context("Failed to stream decompressed data to qemu-img")?;
+        Ok(())
+    });
+
+    let output = child.wait_with_output().context("Failed to wait for qemu-img command")?;
+    writer.join().expect("qemu-img stdin writer thread panicked")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("qemu-img failed: {}", stderr);
+    }
+    println!("{}", String::from_utf8_lossy(&output.stdout));
+
+    Ok(())
+}
+
+/// Extracts the volume id (e.g. `local-lvm:vm-100-disk-0`) that `qm
+/// importdisk` just created for `vmid`, first by parsing its stdout and
+/// falling back to the newest `unusedN` entry in `qm config` if that fails.
+fn resolve_imported_volume_id(importdisk_stdout: &str, vmid: usize) -> Result<String> {
+    if let Some(volume_id) = parse_importdisk_volume_id(importdisk_stdout) {
+        return Ok(volume_id);
+    }
+
+    query_unused_volume_id(vmid).context("Failed to determine the imported disk's volume id")
+}
+
+/// Parses `qm importdisk`'s stdout, which reports the new volume as
+/// `Successfully imported disk as 'unusedN:storage:vm-<vmid>-disk-<n>'`, and
+/// returns the `storage:vm-<vmid>-disk-<n>` part `qm set` expects.
+fn parse_importdisk_volume_id(stdout: &str) -> Option<String> {
+    stdout.lines().find_map(|line| {
+        let quoted = line.split('\'').nth(1)?;
+        let (_unused_slot, volume_id) = quoted.split_once(':')?;
+        Some(volume_id.to_string())
+    })
+}
+
+/// Falls back to `qm config <vmid>` and returns the last `unusedN` volume,
+/// which is the one `qm importdisk` just attached.
+fn query_unused_volume_id(vmid: usize) -> Result<String> {
     let output = Command::new("qm")
-        .arg("importdisk")
-        .arg(parameter.vm_id.to_string())
-        .arg(&vmdisk_name)
-        .arg(parameter.storage)
+        .arg("config")
+        .arg(vmid.to_string())
         .output()
-        .context("Failed to execute qm command")?;
+        .context("Failed to execute qm config command")?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("qm importdisk failed: {}", stderr);
+        bail!("qm config failed: {}", stderr);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(key, _)| key.trim().starts_with("unused"))
+        .last()
+        .map(|(_, value)| value.trim().to_string())
+        .context("Failed to find an 'unused' disk in qm config")
+}
+
+/// Attaches `volume_id` to `vmid` on `bus`/`disk_slot` via `qm set`.
+fn attach_disk(vmid: usize, bus: Bus, disk_slot: u32, volume_id: &str) -> Result<()> {
+    let output = Command::new("qm")
+        .arg("set")
+        .arg(vmid.to_string())
+        .arg(format!("--{}{}", bus.as_str(), disk_slot))
+        .arg(volume_id)
+        .output()
+        .context("Failed to execute qm set command")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("qm set failed: {}", stderr);
     }
     println!("{}", String::from_utf8_lossy(&output.stdout));
 
-    // Clean up temporary files
-    println!("--- remove temp file...");
-    fs::remove_file(&vmdisk_name).context("Failed to remove temporary qcow2 file")?;
+    Ok(())
+}
+
+/// Moves `bus`/`disk_slot` to the front of the VM's boot order via
+/// `qm set --boot`, keeping any other devices already in that order rather
+/// than replacing it outright.
+fn set_boot_disk(vmid: usize, bus: Bus, disk_slot: u32) -> Result<()> {
+    let device = format!("{}{}", bus.as_str(), disk_slot);
 
-    if !is_image_file {
-        fs::remove_file(&processed_image_path).context("Failed to remove decompressed image file")?;
+    let mut order = vec![device.clone()];
+    order.extend(query_boot_order(vmid)?.into_iter().filter(|d| *d != device));
+
+    let output = Command::new("qm")
+        .arg("set")
+        .arg(vmid.to_string())
+        .arg("--boot")
+        .arg(format!("order={}", order.join(";")))
+        .output()
+        .context("Failed to execute qm set command")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("qm set failed: {}", stderr);
     }
+    println!("{}", String::from_utf8_lossy(&output.stdout));
 
-    println!("--- success");
     Ok(())
 }
 
-fn decompress_bz2_file(file_path: PathBuf) -> Result<PathBuf> {
-    println!("decompress bz2 file {}...", file_path.display());
+/// Reads the VM's current `boot: order=...` devices from `qm config`, in
+/// order. Returns an empty list if no boot order is set yet.
+fn query_boot_order(vmid: usize) -> Result<Vec<String>> {
+    let output = Command::new("qm")
+        .arg("config")
+        .arg(vmid.to_string())
+        .output()
+        .context("Failed to execute qm config command")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("qm config failed: {}", stderr);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let boot_line = stdout.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "boot").then(|| value.trim().to_string())
+    });
+
+    let Some(boot_line) = boot_line else {
+        return Ok(Vec::new());
+    };
+
+    Ok(boot_line
+        .strip_prefix("order=")
+        .unwrap_or(&boot_line)
+        .split(';')
+        .filter(|device| !device.is_empty())
+        .map(|device| device.to_string())
+        .collect())
+}
+
+/// Derives a format hint from `file_path`'s name, treating a recognized
+/// compound extension (`tar.gz`, `tar.xz`, `tar.bz2`) as a single unit and
+/// otherwise falling back to the plain last extension (`gz`, `tar`, `img`, ...).
+fn detect_extension_hint(file_path: &Path) -> Option<String> {
+    let name = file_path.file_name()?.to_str()?.to_lowercase();
+
+    for ext in COMPOUND_EXTENSIONS {
+        if name.ends_with(&format!(".{}", ext)) {
+            return Some(ext.to_string());
+        }
+    }
+
+    file_path.extension().and_then(|ext| ext.to_str()).map(|s| s.to_lowercase())
+}
+
+/// Sniffs the first few bytes of `file_path` and matches them against known
+/// compression magic numbers, returning the corresponding extension-style
+/// format tag (e.g. `"gz"`, `"xz"`) on a match, or `None` when the header is
+/// ambiguous and the caller should fall back to the file extension.
+fn detect_format_by_magic(file_path: &Path) -> Result<Option<&'static str>> {
+    let mut file =
+        File::open(file_path).with_context(|| format!("Failed to open file for magic sniffing: {}", file_path.display()))?;
+
+    let mut header = [0u8; 6];
+    let bytes_read = file.read(&mut header).context("Failed to read magic bytes")?;
+    let header = &header[..bytes_read];
+
+    if header.starts_with(&[0x1F, 0x8B]) {
+        Ok(Some("gz"))
+    } else if header.starts_with(b"BZh") {
+        Ok(Some("bz2"))
+    } else if header.starts_with(&[0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00]) {
+        Ok(Some("xz"))
+    } else if header.starts_with(&[0x50, 0x4B, 0x03, 0x04]) {
+        Ok(Some("zip"))
+    } else if header.len() >= 2 && header[0] == 0x5D && header[1] == 0x00 {
+        // The classic LZMA "alone" header: a properties byte (0x5D for the
+        // common lc=3,lp=0,pb=2 default) followed by the lowest byte of the
+        // little-endian dictionary size, which is zero for the usual
+        // power-of-two dictionary sizes.
+        Ok(Some("lzma"))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Opens `file_path` and wraps it in a multi-stream bzip2 decoder, ready to
+/// be streamed straight into `qemu-img` or a temp file.
+fn open_bz2_decoder(file_path: &Path) -> Result<Box<dyn Read + Send>> {
+    let file = File::open(file_path).with_context(|| format!("Failed to open bz2 file: {}", file_path.display()))?;
+    Ok(Box::new(MultiBzDecoder::new(file)))
+}
+
+/// Opens `file_path` and wraps it in a gzip decoder. Uses the multi-stream
+/// variant so concatenated/multi-member `.gz` files are fully decompressed
+/// instead of silently truncated after the first member.
+fn open_gz_decoder(file_path: &Path) -> Result<Box<dyn Read + Send>> {
+    let file = File::open(file_path).with_context(|| format!("Failed to open gz file: {}", file_path.display()))?;
+    Ok(Box::new(MultiGzDecoder::new(file)))
+}
 
-    let file = File::open(&file_path).with_context(|| format!("Failed to open bz2 file: {}", file_path.display()))?;
-    let mut decoder = MultiBzDecoder::new(file);
+/// Opens `file_path` and wraps it in an xz decoder.
+fn open_xz_decoder(file_path: &Path) -> Result<Box<dyn Read + Send>> {
+    let file = File::open(file_path).with_context(|| format!("Failed to open xz file: {}", file_path.display()))?;
+    Ok(Box::new(XzReader::new(file, true)))
+}
 
-    let mut buffer = Vec::new();
-    decoder
-        .read_to_end(&mut buffer)
-        .context("Failed to decompress bz2 file")?;
+/// Opens `file_path` and wraps it in an lzma-alone decoder.
+fn open_lzma_decoder(file_path: &Path) -> Result<Box<dyn Read + Send>> {
+    let file = File::open(file_path).with_context(|| format!("Failed to open lzma file: {}", file_path.display()))?;
+    let reader = LzmaReader::new_mem_limit(file, 64 * 1_024, None).context("Failed to create LZMA reader")?;
+    Ok(Box::new(reader))
+}
+
+fn decompress_bz2_file(file_path: &Path) -> Result<PathBuf> {
+    println!("decompress bz2 file {}...", file_path.display());
+
+    let mut decoder = open_bz2_decoder(file_path)?;
 
     let file_stem = file_path.file_stem().context("Failed to get file stem from bz2 file")?;
     let image_path = WORK_DIR.join(file_stem);
 
     let mut image_file = File::create(&image_path)
         .with_context(|| format!("Failed to create decompressed file: {}", image_path.display()))?;
-    image_file
-        .write_all(&buffer)
-        .context("Failed to write decompressed data")?;
+    io::copy(&mut decoder, &mut image_file).context("Failed to decompress bz2 file")?;
 
     Ok(image_path)
 }
 
-fn decompress_gz_file(file_path: PathBuf) -> Result<PathBuf> {
+fn decompress_gz_file(file_path: &Path) -> Result<PathBuf> {
     println!("decompress gz file {}...", file_path.display());
 
-    let file = File::open(&file_path).with_context(|| format!("Failed to open gz file: {}", file_path.display()))?;
-    let mut decoder = GzDecoder::new(file);
-
-    let mut buffer = Vec::new();
-    decoder
-        .read_to_end(&mut buffer)
-        .context("Failed to decompress gz file")?;
+    let mut decoder = open_gz_decoder(file_path)?;
 
     let file_stem = file_path.file_stem().context("Failed to get file stem from gz file")?;
     let image_path = WORK_DIR.join(file_stem);
 
     let mut image_file = File::create(&image_path)
         .with_context(|| format!("Failed to create decompressed file: {}", image_path.display()))?;
-    image_file
-        .write_all(&buffer)
-        .context("Failed to write decompressed data")?;
+    io::copy(&mut decoder, &mut image_file).context("Failed to decompress gz file")?;
 
     Ok(image_path)
 }
 
-fn decompress_xz_file(file_path: PathBuf) -> Result<PathBuf> {
+fn decompress_xz_file(file_path: &Path) -> Result<PathBuf> {
     println!("decompress xz file {}...", file_path.display());
 
-    let file = File::open(&file_path).with_context(|| format!("Failed to open xz file: {}", file_path.display()))?;
-    let mut reader = XzReader::new(file, true);
-
-    let mut buffer = Vec::new();
-    reader
-        .read_to_end(&mut buffer)
-        .context("Failed to decompress xz file")?;
+    let mut reader = open_xz_decoder(file_path)?;
 
     let file_stem = file_path.file_stem().context("Failed to get file stem from xz file")?;
     let image_path = WORK_DIR.join(file_stem);
 
     let mut image_file = File::create(&image_path)
         .with_context(|| format!("Failed to create decompressed file: {}", image_path.display()))?;
-    image_file
-        .write_all(&buffer)
-        .context("Failed to write decompressed data")?;
+    io::copy(&mut reader, &mut image_file).context("Failed to decompress xz file")?;
 
     Ok(image_path)
 }
 
-fn decompress_lzma_file(file_path: PathBuf) -> Result<PathBuf> {
+fn decompress_lzma_file(file_path: &Path) -> Result<PathBuf> {
     println!("decompress lzma file {}...", file_path.display());
 
-    let file = File::open(&file_path).with_context(|| format!("Failed to open lzma file: {}", file_path.display()))?;
-    let mut reader = LzmaReader::new_mem_limit(file, 64 * 1_024, None).context("Failed to create LZMA reader")?;
-
-    let mut buffer = Vec::new();
-    reader
-        .read_to_end(&mut buffer)
-        .context("Failed to decompress lzma file")?;
+    let mut reader = open_lzma_decoder(file_path)?;
 
     let file_stem = file_path
         .file_stem()
@@ -210,33 +560,225 @@ fn decompress_lzma_file(file_path: PathBuf) -> Result<PathBuf> {
 
     let mut image_file = File::create(&image_path)
         .with_context(|| format!("Failed to create decompressed file: {}", image_path.display()))?;
-    image_file
-        .write_all(&buffer)
-        .context("Failed to write decompressed data")?;
+    io::copy(&mut reader, &mut image_file).context("Failed to decompress lzma file")?;
 
     Ok(image_path)
 }
 
-fn decompress_zip_file(file_path: PathBuf) -> Result<PathBuf> {
+/// Prints every entry in the ZIP archive at `file_path`, with its size and
+/// whether it's a directory, so the user can pick an `--entry` value.
+fn list_zip_entries(file_path: &Path) -> Result<()> {
+    let file = File::open(file_path).with_context(|| format!("Failed to open zip file: {}", file_path.display()))?;
+    let mut archive = ZipArchive::new(&file).context("Failed to read zip archive")?;
+
+    println!("Entries in {}:", file_path.display());
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index(i)
+            .with_context(|| format!("Failed to access entry {} in ZIP archive", i))?;
+        println!(
+            "{:>4}  {:>12}  {}{}",
+            i,
+            entry.size(),
+            if entry.is_dir() { "[dir] " } else { "" },
+            entry.name()
+        );
+    }
+
+    Ok(())
+}
+
+/// Resolves `entry` (a name or index) to an index among `entries` (index,
+/// name, is-directory triples). When `entry` is `None`, auto-picks the
+/// single non-directory member, preferring one ending in `.img`/`.iso` when
+/// several exist, and errors with a listing of the non-directory entries if
+/// the choice is still ambiguous.
+fn select_archive_entry(entries: &[(usize, String, bool)], entry: Option<&str>, archive_kind: &str) -> Result<usize> {
+    if let Some(selector) = entry {
+        if let Ok(index) = selector.parse::<usize>() {
+            if entries.iter().any(|(i, _, _)| *i == index) {
+                return Ok(index);
+            }
+            bail!("Entry index {} is out of range ({} has {} entries)", index, archive_kind, entries.len());
+        }
+
+        return entries
+            .iter()
+            .find(|(_, name, _)| name == selector)
+            .map(|(i, _, _)| *i)
+            .with_context(|| format!("No entry named '{}' in {}", selector, archive_kind));
+    }
+
+    let candidates: Vec<_> = entries.iter().filter(|(_, _, is_dir)| !is_dir).collect();
+
+    if candidates.is_empty() {
+        bail!("{} contains no regular files", archive_kind);
+    }
+
+    if candidates.len() == 1 {
+        return Ok(candidates[0].0);
+    }
+
+    let image_candidates: Vec<_> = candidates
+        .iter()
+        .filter(|(_, name, _)| {
+            let lower = name.to_lowercase();
+            lower.ends_with(".img") || lower.ends_with(".iso")
+        })
+        .collect();
+
+    if image_candidates.len() == 1 {
+        return Ok(image_candidates[0].0);
+    }
+
+    let mut message = format!(
+        "{} contains multiple candidate files; use --entry <name-or-index> to pick one:\n",
+        archive_kind
+    );
+    for (index, name, _) in &candidates {
+        message.push_str(&format!("  {:>4}  {}\n", index, name));
+    }
+    bail!(message);
+}
+
+fn decompress_zip_file(file_path: &Path, entry: Option<&str>) -> Result<PathBuf> {
     println!("decompress zip file {}...", file_path.display());
 
-    let file = File::open(&file_path).with_context(|| format!("Failed to open zip file: {}", file_path.display()))?;
+    let file = File::open(file_path).with_context(|| format!("Failed to open zip file: {}", file_path.display()))?;
     let mut archive = ZipArchive::new(&file).context("Failed to read zip archive")?;
 
     if archive.len() == 0 {
         bail!("ZIP file is empty");
     }
 
+    let mut entries = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let f = archive
+            .by_index(i)
+            .with_context(|| format!("Failed to access entry {} in ZIP archive", i))?;
+        entries.push((i, f.name().to_string(), f.is_dir()));
+    }
+
+    let index = select_archive_entry(&entries, entry, "ZIP archive")?;
     let mut entity = archive
-        .by_index(0)
-        .context("Failed to access first file in ZIP archive")?;
+        .by_index(index)
+        .with_context(|| format!("Failed to access entry {} in ZIP archive", index))?;
 
     let file_stem = file_path.file_stem().context("Failed to get file stem from zip file")?;
     let image_path = WORK_DIR.join(file_stem);
 
     let mut extracted_file = File::create(&image_path)
         .with_context(|| format!("Failed to create extracted file: {}", image_path.display()))?;
-    std::io::copy(&mut entity, &mut extracted_file).context("Failed to extract file from ZIP archive")?;
+    io::copy(&mut entity, &mut extracted_file).context("Failed to extract file from ZIP archive")?;
 
     Ok(image_path)
 }
+
+/// Opens `file_path` and layers a tar reader on top of the decompressor
+/// matching `format` (`"tar"`, `"tar.gz"`, `"tar.xz"`, or `"tar.bz2"`).
+fn open_tar_archive(file_path: &Path, format: &str) -> Result<Archive<Box<dyn Read>>> {
+    let file = File::open(file_path).with_context(|| format!("Failed to open tar file: {}", file_path.display()))?;
+
+    let reader: Box<dyn Read> = match format {
+        "tar" => Box::new(file),
+        "tar.gz" => Box::new(MultiGzDecoder::new(file)),
+        "tar.xz" => Box::new(XzReader::new(file, true)),
+        "tar.bz2" => Box::new(MultiBzDecoder::new(file)),
+        _ => bail!("Unsupported tar format: {}", format),
+    };
+
+    Ok(Archive::new(reader))
+}
+
+/// Prints every entry in the tar archive at `file_path`, with its size and
+/// whether it's a directory, so the user can pick an `--entry` value.
+fn list_tar_entries(file_path: &Path, format: &str) -> Result<()> {
+    let mut archive = open_tar_archive(file_path, format)?;
+
+    println!("Entries in {}:", file_path.display());
+    for (i, entry) in archive.entries().context("Failed to read tar archive")?.enumerate() {
+        let entry = entry.context("Failed to read tar entry")?;
+        let path = entry.path().context("Failed to read tar entry path")?.into_owned();
+        println!(
+            "{:>4}  {:>12}  {}{}",
+            i,
+            entry.header().size().unwrap_or(0),
+            if entry.header().entry_type().is_dir() { "[dir] " } else { "" },
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn decompress_tar_file(file_path: &Path, format: &str, entry: Option<&str>) -> Result<PathBuf> {
+    println!("extract tar file {}...", file_path.display());
+
+    // The underlying decompressed stream can only be read forward once, so
+    // resolving the selection and extracting the chosen entry each need
+    // their own pass over a freshly reopened archive.
+    let mut archive = open_tar_archive(file_path, format)?;
+    let mut entries = Vec::new();
+    for (i, entry) in archive.entries().context("Failed to read tar archive")?.enumerate() {
+        let entry = entry.context("Failed to read tar entry")?;
+        let path = entry.path().context("Failed to read tar entry path")?.into_owned();
+        entries.push((i, path.display().to_string(), entry.header().entry_type().is_dir()));
+    }
+
+    let index = select_archive_entry(&entries, entry, "tar archive")?;
+
+    let file_name = file_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .context("Failed to get file name from tar file")?;
+    let file_stem = file_name.strip_suffix(&format!(".{}", format)).unwrap_or(file_name);
+    let image_path = WORK_DIR.join(file_stem);
+
+    let mut archive = open_tar_archive(file_path, format)?;
+    for (i, entry) in archive.entries().context("Failed to read tar archive")?.enumerate() {
+        let mut entry = entry.context("Failed to read tar entry")?;
+        if i != index {
+            continue;
+        }
+
+        let mut extracted_file = File::create(&image_path)
+            .with_context(|| format!("Failed to create extracted file: {}", image_path.display()))?;
+        io::copy(&mut entry, &mut extracted_file).context("Failed to extract file from tar archive")?;
+
+        return Ok(image_path);
+    }
+
+    bail!("Failed to locate entry {} on the second pass over the tar archive", index);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    #[test]
+    fn open_gz_decoder_reads_every_member_of_a_multi_stream_gz() {
+        let mut data = Vec::new();
+        for chunk in [b"hello " as &[u8], b"world"] {
+            let mut encoder = GzEncoder::new(&mut data, Compression::default());
+            encoder.write_all(chunk).unwrap();
+            encoder.finish().unwrap();
+        }
+
+        let path = std::env::temp_dir().join(format!("img2kvm_test_multi_member_{}.gz", std::process::id()));
+        fs::write(&path, &data).expect("failed to write test gz file");
+
+        let mut decoder = open_gz_decoder(&path).expect("failed to open gz decoder");
+        let mut decompressed = String::new();
+        decoder
+            .read_to_string(&mut decompressed)
+            .expect("failed to decompress multi-member gz file");
+
+        fs::remove_file(&path).expect("failed to remove test gz file");
+
+        assert_eq!(decompressed, "hello world");
+    }
+}